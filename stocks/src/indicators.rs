@@ -0,0 +1,149 @@
+use crate::SeriesEntry;
+
+pub fn sma(entries: &[SeriesEntry], period: usize) -> Vec<Option<f32>> {
+    let mut out = vec![None; entries.len()];
+    if period == 0 {
+        return out;
+    }
+
+    let mut window_sum = 0.0;
+    for (i, entry) in entries.iter().enumerate() {
+        window_sum += entry.close;
+        if i >= period {
+            window_sum -= entries[i - period].close;
+        }
+        if i + 1 >= period {
+            out[i] = Some(window_sum / period as f32);
+        }
+    }
+
+    out
+}
+
+pub fn ema(entries: &[SeriesEntry], period: usize) -> Vec<Option<f32>> {
+    let mut out = vec![None; entries.len()];
+    if period == 0 || entries.len() < period {
+        return out;
+    }
+
+    let seed_index = period - 1;
+    let k = 2.0 / (period as f32 + 1.0);
+
+    let seed = sma(entries, period)[seed_index].unwrap();
+    out[seed_index] = Some(seed);
+
+    let mut prev = seed;
+    for (i, entry) in entries.iter().enumerate().skip(period) {
+        let value = entry.close * k + prev * (1.0 - k);
+        out[i] = Some(value);
+        prev = value;
+    }
+
+    out
+}
+
+pub struct BollingerBands {
+    pub upper: Vec<Option<f32>>,
+    pub middle: Vec<Option<f32>>,
+    pub lower: Vec<Option<f32>>,
+}
+
+pub fn bollinger_bands(entries: &[SeriesEntry], period: usize) -> BollingerBands {
+    let middle = sma(entries, period);
+    let mut upper = vec![None; entries.len()];
+    let mut lower = vec![None; entries.len()];
+
+    if period == 0 {
+        return BollingerBands {
+            upper,
+            middle,
+            lower,
+        };
+    }
+
+    for i in 0..entries.len() {
+        let Some(mean) = middle[i] else {
+            continue;
+        };
+        let window = &entries[i + 1 - period..=i];
+        let variance = window.iter().map(|e| (e.close - mean).powi(2)).sum::<f32>() / period as f32;
+        let std_dev = variance.sqrt();
+
+        upper[i] = Some(mean + 2.0 * std_dev);
+        lower[i] = Some(mean - 2.0 * std_dev);
+    }
+
+    BollingerBands {
+        upper,
+        middle,
+        lower,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(closes: &[f32]) -> Vec<SeriesEntry> {
+        closes
+            .iter()
+            .map(|&close| SeriesEntry {
+                close,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sma_is_none_while_the_window_has_not_filled() {
+        let data = entries(&[1.0, 2.0]);
+        let result = sma(&data, 3);
+        assert_eq!(result, vec![None, None]);
+    }
+
+    #[test]
+    fn sma_emits_once_the_window_fills() {
+        let data = entries(&[1.0, 2.0, 3.0, 4.0]);
+        let result = sma(&data, 2);
+        assert_eq!(result, vec![None, Some(1.5), Some(2.5), Some(3.5)]);
+    }
+
+    #[test]
+    fn ema_is_seeded_from_the_sma_at_the_window_boundary() {
+        let data = entries(&[1.0, 2.0, 3.0, 4.0]);
+        let result = ema(&data, 2);
+
+        // seed_index = period - 1 = 1, seeded from sma([1.0, 2.0]) = 1.5
+        assert_eq!(result[0], None);
+        assert_eq!(result[1], Some(1.5));
+
+        // k = 2 / (period + 1) = 2/3
+        let k = 2.0 / 3.0;
+        let expected_2 = 3.0 * k + 1.5 * (1.0 - k);
+        assert!((result[2].unwrap() - expected_2).abs() < 1e-6);
+
+        let expected_3 = 4.0 * k + expected_2 * (1.0 - k);
+        assert!((result[3].unwrap() - expected_3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ema_is_none_when_there_is_not_enough_data_for_one_window() {
+        let data = entries(&[1.0, 2.0]);
+        let result = ema(&data, 3);
+        assert_eq!(result, vec![None, None]);
+    }
+
+    #[test]
+    fn bollinger_bands_widen_with_variance() {
+        let data = entries(&[2.0, 4.0, 4.0, 4.0]);
+        let bands = bollinger_bands(&data, 4);
+
+        // mean = 3.5, variance = ((1.5)^2 + 3*(0.5)^2) / 4 = 0.75, std_dev = sqrt(0.75)
+        let mean = 3.5_f32;
+        let std_dev = 0.75_f32.sqrt();
+
+        assert_eq!(bands.middle, vec![None, None, None, Some(mean)]);
+        assert!((bands.upper[3].unwrap() - (mean + 2.0 * std_dev)).abs() < 1e-6);
+        assert!((bands.lower[3].unwrap() - (mean - 2.0 * std_dev)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,149 @@
+use std::env;
+
+use crate::SeriesEntry;
+
+#[derive(thiserror::Error, Debug)]
+pub enum InfluxError {
+    #[error("Failed writing points to InfluxDB")]
+    WriteFailed(#[from] Box<ureq::Error>),
+    #[error("Failed reading InfluxDB response")]
+    ResponseReadFailed(#[from] std::io::Error),
+    #[error("Failed parsing InfluxDB query response: {0}")]
+    QueryParseError(String),
+}
+
+pub struct InfluxConfig {
+    url: String,
+    token: String,
+    org: String,
+    bucket: String,
+}
+
+impl InfluxConfig {
+    pub fn from_env() -> Option<InfluxConfig> {
+        Some(InfluxConfig {
+            url: env::var("INFLUXDB_URL").ok()?,
+            token: env::var("INFLUXDB_TOKEN").ok()?,
+            org: env::var("INFLUXDB_ORG").ok()?,
+            bucket: env::var("INFLUXDB_BUCKET").ok()?,
+        })
+    }
+}
+
+fn to_unix_nanos(date: chrono::NaiveDateTime) -> i64 {
+    let utc = date.and_utc();
+    utc.timestamp() * 1_000_000_000 + utc.timestamp_subsec_nanos() as i64
+}
+
+fn to_line_protocol(symbol: &str, entry: &SeriesEntry) -> String {
+    format!(
+        "stock_price,symbol={} open={},high={},low={},close={},volume={}i {}",
+        symbol,
+        entry.open,
+        entry.high,
+        entry.low,
+        entry.close,
+        entry.volume,
+        to_unix_nanos(entry.date)
+    )
+}
+
+pub fn write_points(
+    config: &InfluxConfig,
+    symbol: &str,
+    entries: &[SeriesEntry],
+) -> Result<(), InfluxError> {
+    let body = entries
+        .iter()
+        .map(|entry| to_line_protocol(symbol, entry))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let url = format!("{}/api/v2/write", config.url);
+
+    ureq::post(&url)
+        .query("org", &config.org)
+        .query("bucket", &config.bucket)
+        .query("precision", "ns")
+        .set("Authorization", &format!("Token {}", config.token))
+        .set("Content-Type", "text/plain; charset=utf-8")
+        .send_string(&body)
+        .map_err(Box::new)?;
+
+    Ok(())
+}
+
+pub fn query_points(
+    config: &InfluxConfig,
+    symbol: &str,
+    range_start: &str,
+) -> Result<Vec<SeriesEntry>, InfluxError> {
+    let flux = format!(
+        r#"from(bucket: "{}")
+  |> range(start: {})
+  |> filter(fn: (r) => r._measurement == "stock_price" and r.symbol == "{}")
+  |> pivot(rowKey:["_time"], columnKey: ["_field"], valueColumn: "_value")
+  |> sort(columns: ["_time"])"#,
+        config.bucket, range_start, symbol
+    );
+
+    let url = format!("{}/api/v2/query", config.url);
+
+    let res = ureq::post(&url)
+        .query("org", &config.org)
+        .set("Authorization", &format!("Token {}", config.token))
+        .set("Accept", "application/csv")
+        .set("Content-Type", "application/vnd.flux")
+        .send_string(&flux)
+        .map_err(Box::new)?;
+
+    let body = res.into_string()?;
+    parse_query_response(&body)
+}
+
+fn parse_query_response(body: &str) -> Result<Vec<SeriesEntry>, InfluxError> {
+    let mut entries = vec![];
+
+    let mut lines = body.lines().filter(|line| !line.starts_with('#'));
+    let header = match lines.next() {
+        Some(header) if !header.is_empty() => header,
+        _ => return Ok(entries),
+    };
+    let columns: Vec<&str> = header.split(',').collect();
+
+    let find_col = |name: &str| {
+        columns
+            .iter()
+            .position(|c| *c == name)
+            .ok_or_else(|| InfluxError::QueryParseError(format!("missing column {name}")))
+    };
+
+    let time_col = find_col("_time")?;
+    let open_col = find_col("open")?;
+    let high_col = find_col("high")?;
+    let low_col = find_col("low")?;
+    let close_col = find_col("close")?;
+    let volume_col = find_col("volume")?;
+
+    for line in lines {
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+
+        let date = chrono::DateTime::parse_from_rfc3339(fields[time_col])
+            .map_err(|e| InfluxError::QueryParseError(e.to_string()))?
+            .naive_utc();
+
+        entries.push(SeriesEntry {
+            date,
+            open: fields[open_col].parse().unwrap_or_default(),
+            high: fields[high_col].parse().unwrap_or_default(),
+            low: fields[low_col].parse().unwrap_or_default(),
+            close: fields[close_col].parse().unwrap_or_default(),
+            volume: fields[volume_col].parse().unwrap_or_default(),
+        });
+    }
+
+    Ok(entries)
+}
@@ -8,6 +8,10 @@ use ureq::{Request, Response};
 
 use nannou::prelude::*;
 
+mod indicators;
+mod influx;
+use influx::InfluxConfig;
+
 #[derive(thiserror::Error, Debug)]
 enum AlphaVantageError {
     #[error("Failed fetching issues")]
@@ -16,8 +20,15 @@ enum AlphaVantageError {
     FailedResponseToString(#[from] std::io::Error),
     #[error("Failed parse response to string")]
     IssueDeserialisationError(#[from] serde_json::Error),
+    #[error("Response did not contain a \"Time Series\" entry")]
+    MissingTimeSeries,
+    #[error("Alpha Vantage rate limit hit: {0}")]
+    RateLimited(String),
+    #[error("Alpha Vantage rejected the request: {0}")]
+    InvalidRequest(String),
 }
 
+#[derive(Clone)]
 struct AlphaVantageRequest {
     function: String,
     symbol: String,
@@ -40,9 +51,8 @@ struct TimeSeriesHelper {
     time_series: HashMap<String, HashMap<String, String>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug)]
 struct AlphaVantageResponse {
-    #[serde(rename(deserialize = "Time Series (60min)"))]
     time_series_helper: TimeSeriesHelper,
 }
 
@@ -70,12 +80,66 @@ impl AlphaVantageClient {
 
         let res: Response = req.call()?;
 
-        let res: AlphaVantageResponse = res.into_json()?;
+        let mut raw: HashMap<String, serde_json::Value> = res.into_json()?;
+
+        if let Some(err) = classify_error_response(&raw) {
+            return Err(err);
+        }
+
+        let _meta_data = raw.remove("Meta Data");
+
+        let time_series_key = find_time_series_key(raw.keys())
+            .ok_or(AlphaVantageError::MissingTimeSeries)?;
+        let time_series_value = raw.remove(&time_series_key).unwrap();
 
-        Ok(res)
+        let time_series_helper: TimeSeriesHelper = serde_json::from_value(time_series_value)?;
+
+        Ok(AlphaVantageResponse {
+            time_series_helper,
+        })
+    }
+
+    fn fetch_with_retry(
+        &self,
+        av_req: AlphaVantageRequest,
+        max_retries: u32,
+        retry_delay: std::time::Duration,
+    ) -> Result<AlphaVantageResponse, AlphaVantageError> {
+        let mut attempts = 0;
+        loop {
+            let result = self.fetch(av_req.clone());
+            if !should_retry(&result, attempts, max_retries) {
+                return result;
+            }
+            if let Err(AlphaVantageError::RateLimited(message)) = &result {
+                attempts += 1;
+                eprintln!(
+                    "rate limited ({message}), retrying in {retry_delay:?} ({attempts}/{max_retries})"
+                );
+            }
+            std::thread::sleep(retry_delay);
+        }
     }
 }
 
+/// Classifies Alpha Vantage's "200 OK with an error key" responses (the free
+/// tier never uses real HTTP error codes for rate limiting or bad requests).
+fn classify_error_response(raw: &HashMap<String, serde_json::Value>) -> Option<AlphaVantageError> {
+    for key in ["Note", "Information"] {
+        if let Some(message) = raw.get(key).and_then(|v| v.as_str()) {
+            return Some(AlphaVantageError::RateLimited(message.to_string()));
+        }
+    }
+    if let Some(message) = raw.get("Error Message").and_then(|v| v.as_str()) {
+        return Some(AlphaVantageError::InvalidRequest(message.to_string()));
+    }
+    None
+}
+
+fn should_retry<T>(result: &Result<T, AlphaVantageError>, attempts: u32, max_retries: u32) -> bool {
+    matches!(result, Err(AlphaVantageError::RateLimited(_))) && attempts < max_retries
+}
+
 #[derive(Debug)]
 struct SeriesEntry {
     date: NaiveDateTime,
@@ -99,11 +163,28 @@ impl Default for SeriesEntry {
     }
 }
 
+fn find_time_series_key<'a>(keys: impl Iterator<Item = &'a String>) -> Option<String> {
+    keys.into_iter()
+        .find(|key| key.starts_with("Time Series"))
+        .cloned()
+}
+
+fn parse_entry_date(key: &str) -> NaiveDateTime {
+    if key.contains(' ') {
+        NaiveDateTime::parse_from_str(key, "%Y-%m-%d %H:%M:%S").unwrap()
+    } else {
+        chrono::NaiveDate::parse_from_str(key, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+}
+
 fn parse_response(res: AlphaVantageResponse) -> Vec<SeriesEntry> {
     let mut entities = vec![];
     for (key, value) in &res.time_series_helper.time_series {
         let mut entry: SeriesEntry = SeriesEntry::default();
-        entry.date = NaiveDateTime::parse_from_str(key, "%Y-%m-%d %H:%M:%S").unwrap();
+        entry.date = parse_entry_date(key);
         for (key, data) in value {
             match key.as_str() {
                 "1. open" => entry.open = data.parse().unwrap(),
@@ -130,53 +211,162 @@ fn build_request(function: &str, symbol: &str, interval: &str) -> AlphaVantageRe
 }
 
 fn main() {
-    nannou::app(model).update(update).simple_window(view).run();
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .simple_window(view)
+        .run();
 }
 
-struct Model {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChartMode {
+    Line,
+    Candle,
+}
+
+struct SymbolSeries {
     symbol: String,
     entries: Vec<SeriesEntry>,
 }
 
-fn model(_app: &App) -> Model {
-    _ = dotenv();
+#[derive(Debug, Default)]
+struct IndicatorToggles {
+    sma: bool,
+    ema: bool,
+    bollinger: bool,
+}
+
+struct Model {
+    series: Vec<SymbolSeries>,
+    chart_mode: ChartMode,
+    indicators: IndicatorToggles,
+    indicator_period: usize,
+}
 
-    let key = env::var("API_KEY").unwrap_or_default();
-    let client = AlphaVantageClient::new(key);
+fn is_replay_mode() -> bool {
+    env::args().any(|arg| arg == "--replay") || env::var("REPLAY").is_ok()
+}
 
-    let function = "TIME_SERIES_INTRADAY";
-    let symbol = "TEAM";
-    let interval = "60min";
-    let req = build_request(function, symbol, interval);
+fn model(_app: &App) -> Model {
+    _ = dotenv();
 
-    let res = client.fetch(req).unwrap();
-    let entries = parse_response(res);
+    let symbols_var = env::var("SYMBOLS").unwrap_or_else(|_| "TEAM".to_string());
+    let symbols: Vec<String> = symbols_var
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let influx_config = InfluxConfig::from_env();
+
+    let series = if is_replay_mode() {
+        let config = influx_config
+            .as_ref()
+            .expect("REPLAY mode requires INFLUXDB_URL/TOKEN/ORG/BUCKET to be set");
+        let range_start = env::var("REPLAY_RANGE_START").unwrap_or_else(|_| "-30d".to_string());
+
+        symbols
+            .into_iter()
+            .map(|symbol| {
+                let entries = influx::query_points(config, &symbol, &range_start).unwrap();
+                SymbolSeries { symbol, entries }
+            })
+            .collect()
+    } else {
+        let key = env::var("API_KEY").unwrap_or_default();
+        let client = AlphaVantageClient::new(key);
+
+        let function = "TIME_SERIES_INTRADAY";
+        let interval = "60min";
+
+        let max_retries = env::var("ALPHA_VANTAGE_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+        let retry_delay_secs = env::var("ALPHA_VANTAGE_RETRY_DELAY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(15);
+        let retry_delay = std::time::Duration::from_secs(retry_delay_secs);
+
+        symbols
+            .into_iter()
+            .map(|symbol| {
+                let req = build_request(function, &symbol, interval);
+                let res = client.fetch_with_retry(req, max_retries, retry_delay).unwrap();
+                let entries = parse_response(res);
+
+                if let Some(config) = &influx_config {
+                    influx::write_points(config, &symbol, &entries).unwrap();
+                }
+
+                SymbolSeries { symbol, entries }
+            })
+            .collect()
+    };
 
     Model {
-        symbol: symbol.to_string(),
-        entries,
+        series,
+        chart_mode: ChartMode::Line,
+        indicators: IndicatorToggles::default(),
+        indicator_period: 14,
     }
 }
 
 fn update(_app: &App, _model: &mut Model, _update: Update) {}
 
-fn view(app: &App, _model: &Model, frame: Frame) {
-    let win_rect = app.window_rect();
-
-    app.main_window().set_title("stocks");
-    // get canvas to draw on
-    let draw = app.draw();
+fn event(_app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(key_event),
+        ..
+    } = event
+    {
+        match key_event {
+            KeyPressed(Key::C) => {
+                model.chart_mode = match model.chart_mode {
+                    ChartMode::Line => ChartMode::Candle,
+                    ChartMode::Candle => ChartMode::Line,
+                };
+            }
+            KeyPressed(Key::Key1) => model.indicators.sma = !model.indicators.sma,
+            KeyPressed(Key::Key2) => model.indicators.ema = !model.indicators.ema,
+            KeyPressed(Key::Key3) => model.indicators.bollinger = !model.indicators.bollinger,
+            _ => {}
+        }
+    }
+}
 
-    // set background to blue
-    draw.background().color(DARKBLUE);
+struct ChartParams<'a> {
+    chart_mode: ChartMode,
+    offset: f32,
+    spacing: f32,
+    indicators: &'a IndicatorToggles,
+    indicator_period: usize,
+}
 
-    draw.text(&_model.symbol.to_string())
+fn draw_symbol_band(
+    draw: &Draw,
+    win_rect: Rect,
+    band_center_y: f32,
+    band_height: f32,
+    series: &SymbolSeries,
+    params: &ChartParams,
+) {
+    let ChartParams {
+        chart_mode,
+        offset,
+        spacing,
+        indicators,
+        indicator_period,
+    } = *params;
+
+    let bound_min = band_center_y - band_height * 0.3;
+    let bound_max = band_center_y + band_height * 0.3;
+
+    draw.text(&series.symbol.to_string())
         .x(win_rect.x() / 2.0)
-        .y(300.0)
-        .font_size(70);
-
-    let bound_min = -200.0;
-    let bound_max = 200.0;
+        .y(band_center_y + band_height * 0.42)
+        .font_size(30);
 
     draw.line()
         .start(pt2(0.0, bound_min - 10.0))
@@ -186,12 +376,24 @@ fn view(app: &App, _model: &Model, frame: Frame) {
 
     let mut current_price = 0.0;
     let mut date: NaiveDateTime = NaiveDateTime::default();
-    let close_values: Vec<f32> = _model.entries.iter().map(|a| a.close).collect();
+    let close_values: Vec<f32> = series.entries.iter().map(|a| a.close).collect();
+    let low_values: Vec<f32> = series.entries.iter().map(|a| a.low).collect();
+    let high_values: Vec<f32> = series.entries.iter().map(|a| a.high).collect();
     let min_close = close_values.iter().fold(f32::INFINITY, |a, &b| a.min(b));
     let max_close = close_values
         .iter()
         .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-    let _average_close: f32 = close_values.iter().sum::<f32>() / close_values.len() as f32;
+    let min_low = low_values.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+    let max_high = high_values
+        .iter()
+        .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let (legend_min, legend_max) = match chart_mode {
+        ChartMode::Line => (min_close, max_close),
+        ChartMode::Candle => (min_low, max_high),
+    };
+    let to_y = |value: f32| {
+        ((value - legend_min) / (legend_max - legend_min)) * (bound_max - bound_min) + bound_min
+    };
 
     let legend_x = -win_rect.w() / 2.0 + 50.0;
 
@@ -200,7 +402,7 @@ fn view(app: &App, _model: &Model, frame: Frame) {
         .end(pt2(legend_x - 10.0, bound_min))
         .weight(2.0)
         .color(DARKGRAY);
-    draw.text(&min_close.to_string())
+    draw.text(&legend_min.to_string())
         .x(legend_x)
         .y(bound_min - 20.0)
         .font_size(10);
@@ -210,44 +412,251 @@ fn view(app: &App, _model: &Model, frame: Frame) {
         .end(pt2(legend_x - 10.0, bound_max))
         .weight(2.0)
         .color(DARKGRAY);
-    draw.text(&max_close.to_string())
+    draw.text(&legend_max.to_string())
         .x(legend_x)
         .y(bound_max + 20.0)
         .font_size(10);
 
-    let speed = 70.0;
-    let spacing = 15.0;
-    let offset = -app.time * speed;
+    match chart_mode {
+        ChartMode::Line => {
+            let points = series.entries.iter().enumerate().map(|(i, entry)| {
+                let x = i as f32 * spacing + offset;
 
-    let points = _model.entries.iter().enumerate().map(|(i, entry)| {
-        let x = i as f32 * spacing + offset;
+                let mut color = STEELBLUE;
+                if x < 0.0 {
+                    current_price = entry.close;
+                    date = entry.date;
+                    color = DARKMAGENTA;
+                }
 
-        let mut color = STEELBLUE;
-        if x < 0.0 {
-            current_price = entry.close;
-            date = entry.date;
-            color = DARKMAGENTA;
+                (pt2(x, to_y(entry.close)), color)
+            });
+
+            draw.polyline().weight(3.0).points_colored(points);
         }
+        ChartMode::Candle => {
+            for (i, entry) in series.entries.iter().enumerate() {
+                let x = i as f32 * spacing + offset;
+
+                if x < 0.0 {
+                    current_price = entry.close;
+                    date = entry.date;
+                }
+
+                let color = if entry.close >= entry.open {
+                    GREEN
+                } else {
+                    RED
+                };
+
+                draw.line()
+                    .start(pt2(x, to_y(entry.low)))
+                    .end(pt2(x, to_y(entry.high)))
+                    .weight(1.0)
+                    .color(color);
+
+                let body_top = to_y(entry.open.max(entry.close));
+                let body_bottom = to_y(entry.open.min(entry.close));
+                draw.rect()
+                    .x_y(x, (body_top + body_bottom) / 2.0)
+                    .w_h(spacing * 0.6, (body_top - body_bottom).max(1.0))
+                    .color(color);
+            }
+        }
+    }
 
-        let y = {
-            ((entry.close - min_close) / (max_close - min_close)) * (bound_max - bound_min)
-                + bound_min
-        };
+    if indicators.sma {
+        let sma_values = indicators::sma(&series.entries, indicator_period);
+        let points = sma_values.iter().enumerate().filter_map(|(i, value)| {
+            value.map(|value| (pt2(i as f32 * spacing + offset, to_y(value)), YELLOW))
+        });
+        draw.polyline().weight(2.0).points_colored(points);
+    }
 
-        (pt2(x, y), color)
-    });
+    if indicators.ema {
+        let ema_values = indicators::ema(&series.entries, indicator_period);
+        let points = ema_values.iter().enumerate().filter_map(|(i, value)| {
+            value.map(|value| (pt2(i as f32 * spacing + offset, to_y(value)), ORANGE))
+        });
+        draw.polyline().weight(2.0).points_colored(points);
+    }
 
-    draw.polyline().weight(3.0).points_colored(points);
+    if indicators.bollinger {
+        let bands = indicators::bollinger_bands(&series.entries, indicator_period);
+        let upper_points = bands.upper.iter().enumerate().filter_map(|(i, value)| {
+            value.map(|value| (pt2(i as f32 * spacing + offset, to_y(value)), WHITE))
+        });
+        draw.polyline().weight(1.0).points_colored(upper_points);
+
+        let middle_points = bands.middle.iter().enumerate().filter_map(|(i, value)| {
+            value.map(|value| (pt2(i as f32 * spacing + offset, to_y(value)), LIGHTGRAY))
+        });
+        draw.polyline().weight(1.0).points_colored(middle_points);
+
+        let lower_points = bands.lower.iter().enumerate().filter_map(|(i, value)| {
+            value.map(|value| (pt2(i as f32 * spacing + offset, to_y(value)), WHITE))
+        });
+        draw.polyline().weight(1.0).points_colored(lower_points);
+    }
 
     draw.text(&current_price.to_string())
         .x(win_rect.x() / 2.0)
-        .y(-300.0)
-        .font_size(30);
+        .y(band_center_y - band_height * 0.42)
+        .font_size(20);
 
     draw.text(&date.to_string())
         .x(win_rect.x() / 2.0)
-        .y(-350.0)
-        .font_size(20);
+        .y(band_center_y - band_height * 0.48)
+        .font_size(14);
+}
+
+fn view(app: &App, _model: &Model, frame: Frame) {
+    let win_rect = app.window_rect();
+
+    app.main_window().set_title("stocks");
+    // get canvas to draw on
+    let draw = app.draw();
+
+    // set background to blue
+    draw.background().color(DARKBLUE);
+
+    let speed = 70.0;
+    let spacing = 15.0;
+    let offset = -app.time * speed;
+
+    let band_count = _model.series.len().max(1) as f32;
+    let band_height = win_rect.h() / band_count;
+
+    let params = ChartParams {
+        chart_mode: _model.chart_mode,
+        offset,
+        spacing,
+        indicators: &_model.indicators,
+        indicator_period: _model.indicator_period,
+    };
+
+    for (i, series) in _model.series.iter().enumerate() {
+        let band_top = win_rect.top() - i as f32 * band_height;
+        let band_center_y = band_top - band_height / 2.0;
+
+        draw_symbol_band(&draw, win_rect, band_center_y, band_height, series, &params);
+    }
 
     draw.to_frame(app, &frame).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_time_series_key_picks_the_time_series_entry_over_meta_data() {
+        let keys = vec!["Meta Data".to_string(), "Time Series (60min)".to_string()];
+        assert_eq!(
+            find_time_series_key(keys.iter()),
+            Some("Time Series (60min)".to_string())
+        );
+    }
+
+    #[test]
+    fn find_time_series_key_works_for_daily_and_weekly_suffixes() {
+        let daily = vec!["Meta Data".to_string(), "Time Series (Daily)".to_string()];
+        assert_eq!(
+            find_time_series_key(daily.iter()),
+            Some("Time Series (Daily)".to_string())
+        );
+
+        let weekly = vec![
+            "Meta Data".to_string(),
+            "Weekly Time Series".to_string(),
+            "Time Series (Weekly)".to_string(),
+        ];
+        assert_eq!(
+            find_time_series_key(weekly.iter()),
+            Some("Time Series (Weekly)".to_string())
+        );
+    }
+
+    #[test]
+    fn find_time_series_key_returns_none_when_absent() {
+        let keys = vec!["Meta Data".to_string(), "Note".to_string()];
+        assert_eq!(find_time_series_key(keys.iter()), None);
+    }
+
+    #[test]
+    fn classify_error_response_treats_note_as_rate_limited() {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_value(serde_json::json!({
+            "Note": "Thank you for using Alpha Vantage! Our standard API rate limit is 25 requests per day."
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            classify_error_response(&raw),
+            Some(AlphaVantageError::RateLimited(_))
+        ));
+    }
+
+    #[test]
+    fn classify_error_response_treats_information_as_rate_limited() {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_value(serde_json::json!({
+            "Information": "Thank you for using Alpha Vantage! This is a premium endpoint."
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            classify_error_response(&raw),
+            Some(AlphaVantageError::RateLimited(_))
+        ));
+    }
+
+    #[test]
+    fn classify_error_response_treats_error_message_as_invalid_request() {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_value(serde_json::json!({
+            "Error Message": "the parameter apikey is invalid or missing."
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            classify_error_response(&raw),
+            Some(AlphaVantageError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn classify_error_response_is_none_for_a_normal_time_series_payload() {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_value(serde_json::json!({
+            "Meta Data": {},
+            "Time Series (Daily)": {}
+        }))
+        .unwrap();
+
+        assert!(classify_error_response(&raw).is_none());
+    }
+
+    #[test]
+    fn should_retry_is_true_for_rate_limited_while_attempts_remain() {
+        let result: Result<(), AlphaVantageError> =
+            Err(AlphaVantageError::RateLimited("slow down".to_string()));
+        assert!(should_retry(&result, 0, 3));
+        assert!(!should_retry(&result, 3, 3));
+    }
+
+    #[test]
+    fn should_retry_is_false_for_non_rate_limited_errors() {
+        let result: Result<(), AlphaVantageError> =
+            Err(AlphaVantageError::InvalidRequest("bad apikey".to_string()));
+        assert!(!should_retry(&result, 0, 3));
+    }
+
+    #[test]
+    fn parse_entry_date_handles_intraday_keys_with_a_time_component() {
+        let date = parse_entry_date("2024-01-02 10:30:00");
+        assert_eq!(date.to_string(), "2024-01-02 10:30:00");
+    }
+
+    #[test]
+    fn parse_entry_date_handles_date_only_keys() {
+        let date = parse_entry_date("2024-01-02");
+        assert_eq!(date.to_string(), "2024-01-02 00:00:00");
+    }
+}